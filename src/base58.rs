@@ -0,0 +1,286 @@
+//! Base58Check codec for addresses and extended keys (BIP versions 0x00/0x05).
+
+use crate::{sha256d, BitcoinError, Script};
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Encodes `payload` as standard base58 (big-endian), preserving leading
+/// zero bytes as leading `1` characters.
+pub fn encode(payload: &[u8]) -> String {
+    let zeros = payload.iter().take_while(|&&b| b == 0).count();
+
+    // log(256) / log(58), rounded up.
+    let mut digits = vec![0u8; (payload.len() - zeros) * 138 / 100 + 1];
+
+    let mut length = 0usize;
+    for &byte in &payload[zeros..] {
+        let mut carry = byte as u32;
+        let mut i = 0usize;
+        for digit in digits.iter_mut().rev() {
+            if carry == 0 && i >= length {
+                break;
+            }
+            carry += 256 * (*digit as u32);
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+            i += 1;
+        }
+        length = i;
+    }
+
+    let first_nonzero = digits.iter().position(|&d| d != 0).unwrap_or(digits.len());
+
+    let mut result = String::with_capacity(zeros + (digits.len() - first_nonzero));
+    result.extend(std::iter::repeat_n('1', zeros));
+    result.extend(digits[first_nonzero..].iter().map(|&d| ALPHABET[d as usize] as char));
+    result
+}
+
+/// Decodes a standard base58 string back into its raw bytes.
+pub fn decode(s: &str) -> Result<Vec<u8>, BitcoinError> {
+    let zeros = s.chars().take_while(|&c| c == '1').count();
+
+    // log(58) / log(256), rounded up.
+    let mut bytes = vec![0u8; s.len() * 733 / 1000 + 1];
+
+    let mut length = 0usize;
+    for c in s.chars() {
+        let digit = ALPHABET
+            .iter()
+            .position(|&a| a as char == c)
+            .ok_or(BitcoinError::InvalidFormat)? as u32;
+
+        let mut carry = digit;
+        let mut i = 0usize;
+        for byte in bytes.iter_mut().rev() {
+            if carry == 0 && i >= length {
+                break;
+            }
+            carry += 58 * (*byte as u32);
+            *byte = (carry % 256) as u8;
+            carry /= 256;
+            i += 1;
+        }
+        length = i;
+    }
+
+    let first_nonzero = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+
+    let mut result = vec![0u8; zeros];
+    result.extend_from_slice(&bytes[first_nonzero..]);
+    Ok(result)
+}
+
+/// Appends a 4-byte double-SHA256 checksum to `payload` and base58-encodes the result.
+pub fn encode_check(payload: &[u8]) -> String {
+    let mut extended = payload.to_vec();
+    extended.extend_from_slice(&sha256d(payload)[0..4]);
+    encode(&extended)
+}
+
+/// Reverses [`encode_check`], returning `InvalidFormat` on a checksum mismatch.
+pub fn decode_check(s: &str) -> Result<Vec<u8>, BitcoinError> {
+    let data = decode(s)?;
+    if data.len() < 4 {
+        return Err(BitcoinError::InvalidFormat);
+    }
+
+    let (payload, checksum) = data.split_at(data.len() - 4);
+    if checksum != &sha256d(payload)[0..4] {
+        return Err(BitcoinError::InvalidFormat);
+    }
+
+    Ok(payload.to_vec())
+}
+
+fn hash160(data: &[u8]) -> [u8; 20] {
+    let sha = Sha256::digest(data);
+    let ripemd = Ripemd160::digest(sha);
+    let mut out = [0u8; 20];
+    out.copy_from_slice(&ripemd);
+    out
+}
+
+const OP_DUP: u8 = 0x76;
+const OP_HASH160: u8 = 0xa9;
+const OP_PUSHBYTES_20: u8 = 0x14;
+const OP_EQUALVERIFY: u8 = 0x88;
+const OP_CHECKSIG: u8 = 0xac;
+const OP_EQUAL: u8 = 0x87;
+
+/// Extracts the 20-byte hash already embedded in a canonical P2PKH
+/// scriptPubKey (`OP_DUP OP_HASH160 <20> OP_EQUALVERIFY OP_CHECKSIG`).
+fn extract_p2pkh_hash(script_pubkey: &Script) -> Option<[u8; 20]> {
+    let bytes = &script_pubkey.bytes;
+    if bytes.len() == 25
+        && bytes[0] == OP_DUP
+        && bytes[1] == OP_HASH160
+        && bytes[2] == OP_PUSHBYTES_20
+        && bytes[23] == OP_EQUALVERIFY
+        && bytes[24] == OP_CHECKSIG
+    {
+        let mut hash = [0u8; 20];
+        hash.copy_from_slice(&bytes[3..23]);
+        Some(hash)
+    } else {
+        None
+    }
+}
+
+/// Extracts the 20-byte hash already embedded in a canonical P2SH
+/// scriptPubKey (`OP_HASH160 <20> OP_EQUAL`).
+fn extract_p2sh_hash(script_pubkey: &Script) -> Option<[u8; 20]> {
+    let bytes = &script_pubkey.bytes;
+    if bytes.len() == 23
+        && bytes[0] == OP_HASH160
+        && bytes[1] == OP_PUSHBYTES_20
+        && bytes[22] == OP_EQUAL
+    {
+        let mut hash = [0u8; 20];
+        hash.copy_from_slice(&bytes[2..22]);
+        Some(hash)
+    } else {
+        None
+    }
+}
+
+/// A version-prefixed, Base58Check-encoded hash, e.g. a P2PKH or P2SH address.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Address {
+    pub version: u8,
+    pub hash: [u8; 20],
+}
+
+impl Address {
+    pub const P2PKH_VERSION: u8 = 0x00;
+    pub const P2SH_VERSION: u8 = 0x05;
+
+    pub fn new(version: u8, hash: [u8; 20]) -> Self {
+        Address { version, hash }
+    }
+
+    /// Wraps the HASH160 of `script` under the given version byte. Use this
+    /// for a P2SH redeem script; a scriptPubKey already carries its hash and
+    /// should be unwrapped with [`Address::p2pkh`] instead.
+    pub fn from_script(version: u8, script: &Script) -> Self {
+        Address::new(version, hash160(&script.bytes))
+    }
+
+    /// Builds a P2PKH address from the 20-byte hash embedded in a canonical
+    /// `scriptPubKey`, falling back to hashing the script itself for
+    /// non-standard scripts.
+    pub fn p2pkh(script_pubkey: &Script) -> Self {
+        let hash = extract_p2pkh_hash(script_pubkey).unwrap_or_else(|| hash160(&script_pubkey.bytes));
+        Address::new(Self::P2PKH_VERSION, hash)
+    }
+
+    /// Builds a P2SH address by hashing a redeem script.
+    pub fn p2sh(redeem_script: &Script) -> Self {
+        Address::from_script(Self::P2SH_VERSION, redeem_script)
+    }
+
+    /// Classifies a `scriptPubKey` as canonical P2PKH or P2SH and builds the
+    /// matching address. Returns `None` for any other script type (bare
+    /// multisig, `OP_RETURN`, segwit, etc.) rather than guessing one.
+    pub fn from_script_pubkey(script_pubkey: &Script) -> Option<Self> {
+        if let Some(hash) = extract_p2pkh_hash(script_pubkey) {
+            return Some(Address::new(Self::P2PKH_VERSION, hash));
+        }
+        if let Some(hash) = extract_p2sh_hash(script_pubkey) {
+            return Some(Address::new(Self::P2SH_VERSION, hash));
+        }
+        None
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut payload = vec![self.version];
+        payload.extend_from_slice(&self.hash);
+        write!(f, "{}", encode_check(&payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode_round_trips_arbitrary_bytes() {
+        let payload = [0x00, 0x01, 0x02, 0xFF, 0xFE, 0x00, 0x00];
+        let encoded = encode(&payload);
+        assert_eq!(decode(&encoded).unwrap(), payload);
+    }
+
+    #[test]
+    fn encode_preserves_leading_zero_bytes_as_leading_ones() {
+        assert_eq!(encode(&[0, 0, 0]), "111");
+        assert_eq!(encode(&[]), "");
+    }
+
+    #[test]
+    fn decode_rejects_invalid_alphabet_characters() {
+        // '0', 'O', 'I', 'l' are excluded from the base58 alphabet.
+        assert_eq!(decode("0"), Err(BitcoinError::InvalidFormat));
+    }
+
+    #[test]
+    fn encode_check_decode_check_round_trips() {
+        let payload = [0x00u8; 21];
+        let encoded = encode_check(&payload);
+        assert_eq!(decode_check(&encoded).unwrap(), payload);
+    }
+
+    #[test]
+    fn decode_check_rejects_checksum_mismatch() {
+        let payload = [0x05u8; 21];
+        let mut encoded = encode_check(&payload);
+        // Flip the last character to corrupt the checksum.
+        let flipped = if encoded.ends_with('1') { 'z' } else { '1' };
+        encoded.replace_range(encoded.len() - 1.., &flipped.to_string());
+        assert_eq!(decode_check(&encoded), Err(BitcoinError::InvalidFormat));
+    }
+
+    #[test]
+    fn p2pkh_address_matches_known_vector() {
+        // Hash160 77bff20c60e522dfaa3350c39b030a5d004e839a is the widely used
+        // example P2PKH address 1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2.
+        let script = Script::new(
+            hex::decode("76a91477bff20c60e522dfaa3350c39b030a5d004e839a88ac").unwrap(),
+        );
+        assert_eq!(
+            Address::p2pkh(&script).to_string(),
+            "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"
+        );
+    }
+
+    #[test]
+    fn from_script_pubkey_classifies_p2pkh_p2sh_and_unrecognized() {
+        let p2pkh_script = Script::new(
+            hex::decode("76a91477bff20c60e522dfaa3350c39b030a5d004e839a88ac").unwrap(),
+        );
+        let p2pkh = Address::from_script_pubkey(&p2pkh_script).unwrap();
+        assert_eq!(p2pkh.version, Address::P2PKH_VERSION);
+
+        let p2sh_script =
+            Script::new(hex::decode("a91477bff20c60e522dfaa3350c39b030a5d004e839a87").unwrap());
+        let p2sh = Address::from_script_pubkey(&p2sh_script).unwrap();
+        assert_eq!(p2sh.version, Address::P2SH_VERSION);
+        assert_eq!(p2sh.hash, p2pkh.hash);
+
+        // OP_RETURN data carrier: not a hash-based script at all.
+        let op_return = Script::new(hex::decode("6a0548656c6c6f").unwrap());
+        assert_eq!(Address::from_script_pubkey(&op_return), None);
+    }
+
+    #[test]
+    fn p2sh_hashes_the_redeem_script() {
+        let redeem_script = Script::new(vec![0x51]); // OP_1
+        let address = Address::p2sh(&redeem_script);
+        assert_eq!(address.version, Address::P2SH_VERSION);
+        assert_eq!(address.hash, hash160(&redeem_script.bytes));
+    }
+}
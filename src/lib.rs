@@ -1,9 +1,21 @@
 
 
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::fmt;
 use std::ops::Deref;
 
+pub mod base58;
+
+/// Double-SHA256, the hash used throughout Bitcoin for block and transaction identity.
+pub(crate) fn sha256d(data: &[u8]) -> [u8; 32] {
+    let first = Sha256::digest(data);
+    let second = Sha256::digest(first);
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&second);
+    out
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct CompactSize {
     pub value: u64,
@@ -15,14 +27,72 @@ pub enum BitcoinError {
     InvalidFormat,
 }
 
-impl CompactSize {
-    pub fn new(value: u64) -> Self {
-        CompactSize { value }
+/// Streaming serialization, mirroring rust-bitcoin's `ConsensusEncodable`.
+pub trait Encodable {
+    fn consensus_encode<W: std::io::Write>(&self, w: &mut W) -> Result<usize, BitcoinError>;
+}
+
+/// Streaming deserialization, mirroring rust-bitcoin's `ConsensusDecodable`.
+pub trait Decodable: Sized {
+    fn consensus_decode<R: std::io::Read>(r: &mut R) -> Result<Self, BitcoinError>;
+}
+
+fn read_exact<R: std::io::Read>(r: &mut R, buf: &mut [u8]) -> Result<(), BitcoinError> {
+    r.read_exact(buf).map_err(|_| BitcoinError::InsufficientBytes)
+}
+
+fn write_all<W: std::io::Write>(w: &mut W, buf: &[u8]) -> Result<(), BitcoinError> {
+    w.write_all(buf).map_err(|_| BitcoinError::InvalidFormat)
+}
+
+/// Reads exactly `length` bytes without trusting `length` as an upfront
+/// allocation size: a corrupt or adversarial `CompactSize` prefix (e.g.
+/// `u64::MAX`) must not crash the process. `Read::take` caps how many
+/// bytes the reader can ever produce, so `read_to_end` grows the buffer
+/// incrementally off what's actually available and stops short instead of
+/// pre-allocating the attacker-supplied size.
+fn read_vec<R: std::io::Read>(r: &mut R, length: usize) -> Result<Vec<u8>, BitcoinError> {
+    use std::io::Read as _;
+
+    let mut buf = Vec::new();
+    let mut limited = r.take(length as u64);
+    limited
+        .read_to_end(&mut buf)
+        .map_err(|_| BitcoinError::InvalidFormat)?;
+    if buf.len() != length {
+        return Err(BitcoinError::InsufficientBytes);
     }
+    Ok(buf)
+}
 
-    pub fn to_bytes(&self) -> Vec<u8> {
+fn decode_compact_size_tail<R: std::io::Read>(
+    first_byte: u8,
+    r: &mut R,
+) -> Result<CompactSize, BitcoinError> {
+    match first_byte {
+        0x00..=0xFC => Ok(CompactSize::new(first_byte as u64)),
+        0xFD => {
+            let mut buf = [0u8; 2];
+            read_exact(r, &mut buf)?;
+            Ok(CompactSize::new(u16::from_le_bytes(buf) as u64))
+        }
+        0xFE => {
+            let mut buf = [0u8; 4];
+            read_exact(r, &mut buf)?;
+            Ok(CompactSize::new(u32::from_le_bytes(buf) as u64))
+        }
+        0xFF => {
+            let mut buf = [0u8; 8];
+            read_exact(r, &mut buf)?;
+            Ok(CompactSize::new(u64::from_le_bytes(buf)))
+        }
+    }
+}
+
+impl Encodable for CompactSize {
+    fn consensus_encode<W: std::io::Write>(&self, w: &mut W) -> Result<usize, BitcoinError> {
         let value = self.value;
-        if value <= 0xFC {
+        let bytes = if value <= 0xFC {
             vec![value as u8]
         } else if value <= 0xFFFF {
             let mut bytes = vec![0xFD];
@@ -36,48 +106,67 @@ impl CompactSize {
             let mut bytes = vec![0xFF];
             bytes.extend_from_slice(&value.to_le_bytes());
             bytes
-        }
+        };
+        write_all(w, &bytes)?;
+        Ok(bytes.len())
     }
+}
 
-    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        if bytes.is_empty() {
-            return Err(BitcoinError::InsufficientBytes);
-        }
+impl Decodable for CompactSize {
+    fn consensus_decode<R: std::io::Read>(r: &mut R) -> Result<Self, BitcoinError> {
+        let mut first_byte = [0u8; 1];
+        read_exact(r, &mut first_byte)?;
+        decode_compact_size_tail(first_byte[0], r)
+    }
+}
 
-        let first_byte = bytes[0];
-        match first_byte {
-            0x00..=0xFC => Ok((CompactSize::new(first_byte as u64), 1)),
-            0xFD => {
-                if bytes.len() < 3 {
-                    return Err(BitcoinError::InsufficientBytes);
-                }
-                let value = u16::from_le_bytes([bytes[1], bytes[2]]) as u64;
-                Ok((CompactSize::new(value), 3))
-            }
-            0xFE => {
-                if bytes.len() < 5 {
-                    return Err(BitcoinError::InsufficientBytes);
-                }
-                let value = u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]) as u64;
-                Ok((CompactSize::new(value), 5))
-            }
-            0xFF => {
-                if bytes.len() < 9 {
-                    return Err(BitcoinError::InsufficientBytes);
-                }
-                let value = u64::from_le_bytes([
-                    bytes[1], bytes[2], bytes[3], bytes[4],
-                    bytes[5], bytes[6], bytes[7], bytes[8],
-                ]);
-                Ok((CompactSize::new(value), 9))
-            }
-        }
+impl CompactSize {
+    pub fn new(value: u64) -> Self {
+        CompactSize { value }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.consensus_encode(&mut buf)
+            .expect("encoding to a Vec<u8> cannot fail");
+        buf
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        let mut cursor = std::io::Cursor::new(bytes);
+        let value = Self::consensus_decode(&mut cursor)?;
+        Ok((value, cursor.position() as usize))
     }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Txid(pub [u8; 32]);
 
+impl Txid {
+    /// Hex encoding in Bitcoin's display order (byte-reversed relative to
+    /// the internal, little-endian serialization order used by `Serialize`).
+    pub fn to_hex(&self) -> String {
+        let mut reversed = self.0;
+        reversed.reverse();
+        hex::encode(reversed)
+    }
+}
+
+impl Encodable for Txid {
+    fn consensus_encode<W: std::io::Write>(&self, w: &mut W) -> Result<usize, BitcoinError> {
+        write_all(w, &self.0)?;
+        Ok(32)
+    }
+}
+
+impl Decodable for Txid {
+    fn consensus_decode<R: std::io::Read>(r: &mut R) -> Result<Self, BitcoinError> {
+        let mut bytes = [0u8; 32];
+        read_exact(r, &mut bytes)?;
+        Ok(Txid(bytes))
+    }
+}
+
 impl Serialize for Txid {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -110,6 +199,27 @@ pub struct OutPoint {
     pub vout: u32,
 }
 
+impl Encodable for OutPoint {
+    fn consensus_encode<W: std::io::Write>(&self, w: &mut W) -> Result<usize, BitcoinError> {
+        let mut n = self.txid.consensus_encode(w)?;
+        write_all(w, &self.vout.to_le_bytes())?;
+        n += 4;
+        Ok(n)
+    }
+}
+
+impl Decodable for OutPoint {
+    fn consensus_decode<R: std::io::Read>(r: &mut R) -> Result<Self, BitcoinError> {
+        let txid = Txid::consensus_decode(r)?;
+        let mut vout_buf = [0u8; 4];
+        read_exact(r, &mut vout_buf)?;
+        Ok(OutPoint {
+            txid,
+            vout: u32::from_le_bytes(vout_buf),
+        })
+    }
+}
+
 impl OutPoint {
     pub fn new(txid: [u8; 32], vout: u32) -> Self {
         OutPoint {
@@ -119,22 +229,16 @@ impl OutPoint {
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        bytes.extend_from_slice(&self.txid.0);
-        bytes.extend_from_slice(&self.vout.to_le_bytes());
-        bytes
+        let mut buf = Vec::new();
+        self.consensus_encode(&mut buf)
+            .expect("encoding to a Vec<u8> cannot fail");
+        buf
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        if bytes.len() < 36 {
-            return Err(BitcoinError::InsufficientBytes);
-        }
-        
-        let mut txid = [0u8; 32];
-        txid.copy_from_slice(&bytes[0..32]);
-        let vout = u32::from_le_bytes([bytes[32], bytes[33], bytes[34], bytes[35]]);
-        
-        Ok((OutPoint::new(txid, vout), 36))
+        let mut cursor = std::io::Cursor::new(bytes);
+        let value = Self::consensus_decode(&mut cursor)?;
+        Ok((value, cursor.position() as usize))
     }
 }
 
@@ -143,30 +247,39 @@ pub struct Script {
     pub bytes: Vec<u8>,
 }
 
+impl Encodable for Script {
+    fn consensus_encode<W: std::io::Write>(&self, w: &mut W) -> Result<usize, BitcoinError> {
+        let mut n = CompactSize::new(self.bytes.len() as u64).consensus_encode(w)?;
+        write_all(w, &self.bytes)?;
+        n += self.bytes.len();
+        Ok(n)
+    }
+}
+
+impl Decodable for Script {
+    fn consensus_decode<R: std::io::Read>(r: &mut R) -> Result<Self, BitcoinError> {
+        let length = CompactSize::consensus_decode(r)?;
+        let bytes = read_vec(r, length.value as usize)?;
+        Ok(Script::new(bytes))
+    }
+}
+
 impl Script {
     pub fn new(bytes: Vec<u8>) -> Self {
         Script { bytes }
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
-        let length = CompactSize::new(self.bytes.len() as u64);
-        let mut result = length.to_bytes();
-        result.extend_from_slice(&self.bytes);
-        result
+        let mut buf = Vec::new();
+        self.consensus_encode(&mut buf)
+            .expect("encoding to a Vec<u8> cannot fail");
+        buf
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        let (length, length_consumed) = CompactSize::from_bytes(bytes)?;
-        let script_length = length.value as usize;
-        
-        if bytes.len() < length_consumed + script_length {
-            return Err(BitcoinError::InsufficientBytes);
-        }
-        
-        let script_bytes = bytes[length_consumed..length_consumed + script_length].to_vec();
-        let total_consumed = length_consumed + script_length;
-        
-        Ok((Script::new(script_bytes), total_consumed))
+        let mut cursor = std::io::Cursor::new(bytes);
+        let value = Self::consensus_decode(&mut cursor)?;
+        Ok((value, cursor.position() as usize))
     }
 }
 
@@ -177,6 +290,52 @@ impl Deref for Script {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct TxOut {
+    pub value: u64,
+    pub script_pubkey: Script,
+}
+
+impl Encodable for TxOut {
+    fn consensus_encode<W: std::io::Write>(&self, w: &mut W) -> Result<usize, BitcoinError> {
+        write_all(w, &self.value.to_le_bytes())?;
+        let mut n = 8;
+        n += self.script_pubkey.consensus_encode(w)?;
+        Ok(n)
+    }
+}
+
+impl Decodable for TxOut {
+    fn consensus_decode<R: std::io::Read>(r: &mut R) -> Result<Self, BitcoinError> {
+        let mut value_buf = [0u8; 8];
+        read_exact(r, &mut value_buf)?;
+        let script_pubkey = Script::consensus_decode(r)?;
+        Ok(TxOut::new(u64::from_le_bytes(value_buf), script_pubkey))
+    }
+}
+
+impl TxOut {
+    pub fn new(value: u64, script_pubkey: Script) -> Self {
+        TxOut {
+            value,
+            script_pubkey,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.consensus_encode(&mut buf)
+            .expect("encoding to a Vec<u8> cannot fail");
+        buf
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        let mut cursor = std::io::Cursor::new(bytes);
+        let value = Self::consensus_decode(&mut cursor)?;
+        Ok((value, cursor.position() as usize))
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct TransactionInput {
     pub previous_output: OutPoint,
@@ -184,6 +343,30 @@ pub struct TransactionInput {
     pub sequence: u32,
 }
 
+impl Encodable for TransactionInput {
+    fn consensus_encode<W: std::io::Write>(&self, w: &mut W) -> Result<usize, BitcoinError> {
+        let mut n = self.previous_output.consensus_encode(w)?;
+        n += self.script_sig.consensus_encode(w)?;
+        write_all(w, &self.sequence.to_le_bytes())?;
+        n += 4;
+        Ok(n)
+    }
+}
+
+impl Decodable for TransactionInput {
+    fn consensus_decode<R: std::io::Read>(r: &mut R) -> Result<Self, BitcoinError> {
+        let previous_output = OutPoint::consensus_decode(r)?;
+        let script_sig = Script::consensus_decode(r)?;
+        let mut sequence_buf = [0u8; 4];
+        read_exact(r, &mut sequence_buf)?;
+        Ok(TransactionInput::new(
+            previous_output,
+            script_sig,
+            u32::from_le_bytes(sequence_buf),
+        ))
+    }
+}
+
 impl TransactionInput {
     pub fn new(previous_output: OutPoint, script_sig: Script, sequence: u32) -> Self {
         TransactionInput {
@@ -194,106 +377,248 @@ impl TransactionInput {
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        bytes.extend_from_slice(&self.previous_output.to_bytes());
-        bytes.extend_from_slice(&self.script_sig.to_bytes());
-        bytes.extend_from_slice(&self.sequence.to_le_bytes());
-        bytes
+        let mut buf = Vec::new();
+        self.consensus_encode(&mut buf)
+            .expect("encoding to a Vec<u8> cannot fail");
+        buf
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        let mut offset = 0;
-        
-        // Parse OutPoint
-        let (previous_output, outpoint_consumed) = OutPoint::from_bytes(&bytes[offset..])?;
-        offset += outpoint_consumed;
-        
-        // Parse Script
-        let (script_sig, script_consumed) = Script::from_bytes(&bytes[offset..])?;
-        offset += script_consumed;
-        
-        // Parse sequence
-        if bytes.len() < offset + 4 {
-            return Err(BitcoinError::InsufficientBytes);
+        let mut cursor = std::io::Cursor::new(bytes);
+        let value = Self::consensus_decode(&mut cursor)?;
+        Ok((value, cursor.position() as usize))
+    }
+}
+
+/// BIP141 witness stack of a single input: its stack items, each a raw byte vector.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Witness(pub Vec<Vec<u8>>);
+
+impl Encodable for Witness {
+    fn consensus_encode<W: std::io::Write>(&self, w: &mut W) -> Result<usize, BitcoinError> {
+        let mut n = CompactSize::new(self.0.len() as u64).consensus_encode(w)?;
+        for item in &self.0 {
+            n += CompactSize::new(item.len() as u64).consensus_encode(w)?;
+            write_all(w, item)?;
+            n += item.len();
         }
-        let sequence = u32::from_le_bytes([
-            bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]
-        ]);
-        offset += 4;
-        
-        Ok((TransactionInput::new(previous_output, script_sig, sequence), offset))
+        Ok(n)
+    }
+}
+
+impl Decodable for Witness {
+    fn consensus_decode<R: std::io::Read>(r: &mut R) -> Result<Self, BitcoinError> {
+        let item_count = CompactSize::consensus_decode(r)?;
+        let mut items = Vec::new();
+        for _ in 0..item_count.value {
+            let item_length = CompactSize::consensus_decode(r)?;
+            items.push(read_vec(r, item_length.value as usize)?);
+        }
+        Ok(Witness::new(items))
     }
 }
 
+impl Witness {
+    pub fn new(items: Vec<Vec<u8>>) -> Self {
+        Witness(items)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.consensus_encode(&mut buf)
+            .expect("encoding to a Vec<u8> cannot fail");
+        buf
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        let mut cursor = std::io::Cursor::new(bytes);
+        let value = Self::consensus_decode(&mut cursor)?;
+        Ok((value, cursor.position() as usize))
+    }
+}
+
+const SEGWIT_MARKER: u8 = 0x00;
+const SEGWIT_FLAG: u8 = 0x01;
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct BitcoinTransaction {
     pub version: u32,
     pub inputs: Vec<TransactionInput>,
+    pub outputs: Vec<TxOut>,
     pub lock_time: u32,
+    /// One witness stack per input, parallel to `inputs`. Empty stacks for
+    /// every input mean the transaction serializes in the legacy format.
+    pub witnesses: Vec<Witness>,
 }
 
 impl BitcoinTransaction {
-    pub fn new(version: u32, inputs: Vec<TransactionInput>, lock_time: u32) -> Self {
+    pub fn new(
+        version: u32,
+        inputs: Vec<TransactionInput>,
+        outputs: Vec<TxOut>,
+        lock_time: u32,
+    ) -> Self {
+        let witnesses = inputs.iter().map(|_| Witness::new(Vec::new())).collect();
         BitcoinTransaction {
             version,
             inputs,
+            outputs,
             lock_time,
+            witnesses,
         }
     }
 
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        
-        // Version
-        bytes.extend_from_slice(&self.version.to_le_bytes());
-        
-        // Number of inputs
-        let input_count = CompactSize::new(self.inputs.len() as u64);
-        bytes.extend_from_slice(&input_count.to_bytes());
-        
-        // Inputs
+    pub fn new_with_witnesses(
+        version: u32,
+        inputs: Vec<TransactionInput>,
+        outputs: Vec<TxOut>,
+        lock_time: u32,
+        witnesses: Vec<Witness>,
+    ) -> Self {
+        BitcoinTransaction {
+            version,
+            inputs,
+            outputs,
+            lock_time,
+            witnesses,
+        }
+    }
+
+    fn has_witness_data(&self) -> bool {
+        self.witnesses.iter().any(|w| !w.is_empty())
+    }
+
+    fn encode_inputs_and_outputs<W: std::io::Write>(&self, w: &mut W) -> Result<usize, BitcoinError> {
+        let mut n = CompactSize::new(self.inputs.len() as u64).consensus_encode(w)?;
         for input in &self.inputs {
-            bytes.extend_from_slice(&input.to_bytes());
+            n += input.consensus_encode(w)?;
         }
-        
-        // Lock time
+
+        n += CompactSize::new(self.outputs.len() as u64).consensus_encode(w)?;
+        for output in &self.outputs {
+            n += output.consensus_encode(w)?;
+        }
+
+        Ok(n)
+    }
+
+    /// Serializes the transaction without any segwit marker/flag or witness
+    /// data, as used for `txid` computation (BIP141).
+    fn to_bytes_legacy(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        self.encode_inputs_and_outputs(&mut bytes)
+            .expect("encoding to a Vec<u8> cannot fail");
         bytes.extend_from_slice(&self.lock_time.to_le_bytes());
-        
         bytes
     }
 
+    /// Double-SHA256 of the legacy serialization, matching `Sha256dHash`-based
+    /// transaction identity.
+    pub fn txid(&self) -> Txid {
+        Txid(sha256d(&self.to_bytes_legacy()))
+    }
+
+    /// Double-SHA256 of the full (segwit) serialization.
+    pub fn wtxid(&self) -> Txid {
+        Txid(sha256d(&self.to_bytes()))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.consensus_encode(&mut buf)
+            .expect("encoding to a Vec<u8> cannot fail");
+        buf
+    }
+
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        let mut offset = 0;
-        
-        // Parse version
-        if bytes.len() < 4 {
-            return Err(BitcoinError::InsufficientBytes);
+        let mut cursor = std::io::Cursor::new(bytes);
+        let value = Self::consensus_decode(&mut cursor)?;
+        Ok((value, cursor.position() as usize))
+    }
+}
+
+impl Encodable for BitcoinTransaction {
+    fn consensus_encode<W: std::io::Write>(&self, w: &mut W) -> Result<usize, BitcoinError> {
+        let mut n = 0;
+        write_all(w, &self.version.to_le_bytes())?;
+        n += 4;
+
+        let is_segwit = self.has_witness_data();
+        if is_segwit {
+            write_all(w, &[SEGWIT_MARKER, SEGWIT_FLAG])?;
+            n += 2;
         }
-        let version = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-        offset += 4;
-        
-        // Parse input count
-        let (input_count, count_consumed) = CompactSize::from_bytes(&bytes[offset..])?;
-        offset += count_consumed;
-        
-        // Parse inputs
+
+        n += self.encode_inputs_and_outputs(w)?;
+
+        if is_segwit {
+            for witness in &self.witnesses {
+                n += witness.consensus_encode(w)?;
+            }
+        }
+
+        write_all(w, &self.lock_time.to_le_bytes())?;
+        n += 4;
+
+        Ok(n)
+    }
+}
+
+impl Decodable for BitcoinTransaction {
+    fn consensus_decode<R: std::io::Read>(r: &mut R) -> Result<Self, BitcoinError> {
+        let mut version_buf = [0u8; 4];
+        read_exact(r, &mut version_buf)?;
+        let version = u32::from_le_bytes(version_buf);
+
+        // The marker/flag and the legacy input CompactSize both start with a
+        // single byte, so read it once and branch on its value.
+        let mut next_byte = [0u8; 1];
+        read_exact(r, &mut next_byte)?;
+
+        let (is_segwit, input_count) = if next_byte[0] == SEGWIT_MARKER {
+            let mut flag = [0u8; 1];
+            read_exact(r, &mut flag)?;
+            if flag[0] != SEGWIT_FLAG {
+                return Err(BitcoinError::InvalidFormat);
+            }
+            (true, CompactSize::consensus_decode(r)?)
+        } else {
+            (false, decode_compact_size_tail(next_byte[0], r)?)
+        };
+
         let mut inputs = Vec::new();
         for _ in 0..input_count.value {
-            let (input, input_consumed) = TransactionInput::from_bytes(&bytes[offset..])?;
-            inputs.push(input);
-            offset += input_consumed;
+            inputs.push(TransactionInput::consensus_decode(r)?);
         }
-        
-        // Parse lock time
-        if bytes.len() < offset + 4 {
-            return Err(BitcoinError::InsufficientBytes);
+
+        let output_count = CompactSize::consensus_decode(r)?;
+        let mut outputs = Vec::new();
+        for _ in 0..output_count.value {
+            outputs.push(TxOut::consensus_decode(r)?);
         }
-        let lock_time = u32::from_le_bytes([
-            bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]
-        ]);
-        offset += 4;
-        
-        Ok((BitcoinTransaction::new(version, inputs, lock_time), offset))
+
+        let witnesses = if is_segwit {
+            let mut witnesses = Vec::new();
+            for _ in 0..inputs.len() {
+                witnesses.push(Witness::consensus_decode(r)?);
+            }
+            witnesses
+        } else {
+            inputs.iter().map(|_| Witness::new(Vec::new())).collect()
+        };
+
+        let mut lock_time_buf = [0u8; 4];
+        read_exact(r, &mut lock_time_buf)?;
+        let lock_time = u32::from_le_bytes(lock_time_buf);
+
+        Ok(BitcoinTransaction::new_with_witnesses(
+            version, inputs, outputs, lock_time, witnesses,
+        ))
     }
 }
 
@@ -312,8 +637,271 @@ impl fmt::Display for BitcoinTransaction {
             writeln!(f, "      Sequence: 0x{:08X}", input.sequence)?;
         }
         
+        writeln!(f, "  Outputs: {}", self.outputs.len())?;
+
+        for (i, output) in self.outputs.iter().enumerate() {
+            writeln!(f, "    Output {}:", i)?;
+            writeln!(f, "      Value: {}", output.value)?;
+            writeln!(f, "      Script Pubkey: {}", hex::encode(&output.script_pubkey.bytes))?;
+            match base58::Address::from_script_pubkey(&output.script_pubkey) {
+                Some(address) => writeln!(f, "      Address: {}", address)?,
+                None => writeln!(f, "      Address: (unrecognized script type)")?,
+            }
+        }
+
         writeln!(f, "  Lock Time: {}", self.lock_time)?;
-        
+
         Ok(())
     }
+}
+
+/// Places a big-endian 24-bit mantissa into a 256-bit big-endian buffer,
+/// shifted by `shift` whole bytes (negative shifts drop low-order bytes).
+fn place_mantissa(mantissa_bytes: [u8; 3], shift: i32, target: &mut [u8; 32]) {
+    for (i, byte) in mantissa_bytes.iter().enumerate() {
+        let position_from_lsb = (2 - i as i32) + shift;
+        if (0..32).contains(&position_from_lsb) {
+            target[31 - position_from_lsb as usize] = *byte;
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub version: u32,
+    pub prev_blockhash: [u8; 32],
+    pub merkle_root: [u8; 32],
+    pub time: u32,
+    pub bits: u32,
+    pub nonce: u32,
+}
+
+impl Encodable for BlockHeader {
+    fn consensus_encode<W: std::io::Write>(&self, w: &mut W) -> Result<usize, BitcoinError> {
+        write_all(w, &self.version.to_le_bytes())?;
+        write_all(w, &self.prev_blockhash)?;
+        write_all(w, &self.merkle_root)?;
+        write_all(w, &self.time.to_le_bytes())?;
+        write_all(w, &self.bits.to_le_bytes())?;
+        write_all(w, &self.nonce.to_le_bytes())?;
+        Ok(80)
+    }
+}
+
+impl Decodable for BlockHeader {
+    fn consensus_decode<R: std::io::Read>(r: &mut R) -> Result<Self, BitcoinError> {
+        let mut version_buf = [0u8; 4];
+        read_exact(r, &mut version_buf)?;
+
+        let mut prev_blockhash = [0u8; 32];
+        read_exact(r, &mut prev_blockhash)?;
+
+        let mut merkle_root = [0u8; 32];
+        read_exact(r, &mut merkle_root)?;
+
+        let mut time_buf = [0u8; 4];
+        read_exact(r, &mut time_buf)?;
+
+        let mut bits_buf = [0u8; 4];
+        read_exact(r, &mut bits_buf)?;
+
+        let mut nonce_buf = [0u8; 4];
+        read_exact(r, &mut nonce_buf)?;
+
+        Ok(BlockHeader {
+            version: u32::from_le_bytes(version_buf),
+            prev_blockhash,
+            merkle_root,
+            time: u32::from_le_bytes(time_buf),
+            bits: u32::from_le_bytes(bits_buf),
+            nonce: u32::from_le_bytes(nonce_buf),
+        })
+    }
+}
+
+impl BlockHeader {
+    pub fn new(
+        version: u32,
+        prev_blockhash: [u8; 32],
+        merkle_root: [u8; 32],
+        time: u32,
+        bits: u32,
+        nonce: u32,
+    ) -> Self {
+        BlockHeader {
+            version,
+            prev_blockhash,
+            merkle_root,
+            time,
+            bits,
+            nonce,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.consensus_encode(&mut buf)
+            .expect("encoding to a Vec<u8> cannot fail");
+        buf
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        let mut cursor = std::io::Cursor::new(bytes);
+        let value = Self::consensus_decode(&mut cursor)?;
+        Ok((value, cursor.position() as usize))
+    }
+
+    /// Expands the compact `bits` field into the full 256-bit target,
+    /// big-endian. The top byte of `bits` is the exponent `e` and the low
+    /// three bytes are the mantissa `m`: target = m << 8*(e-3) for e >= 3,
+    /// or m >> 8*(3-e) otherwise. A set sign bit on the mantissa
+    /// (`m > 0x7FFFFF`) clamps the target to zero.
+    pub fn target(&self) -> [u8; 32] {
+        let exponent = (self.bits >> 24) as i32;
+        let mantissa = self.bits & 0x00FF_FFFF;
+
+        let mut target = [0u8; 32];
+        if mantissa > 0x007F_FFFF {
+            return target;
+        }
+
+        let mantissa_bytes = [
+            ((mantissa >> 16) & 0xFF) as u8,
+            ((mantissa >> 8) & 0xFF) as u8,
+            (mantissa & 0xFF) as u8,
+        ];
+        place_mantissa(mantissa_bytes, exponent - 3, &mut target);
+        target
+    }
+
+    /// Double-SHA256 of the 80-byte header, read as a little-endian 256-bit
+    /// integer, checked to be `<=` the big-endian value from `target`.
+    pub fn validate_pow(&self) -> bool {
+        let mut hash = sha256d(&self.to_bytes());
+        hash.reverse();
+        hash <= self.target()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_input() -> TransactionInput {
+        TransactionInput::new(
+            OutPoint::new([7u8; 32], 0),
+            Script::new(vec![0x51]),
+            0xFFFFFFFF,
+        )
+    }
+
+    fn sample_output() -> TxOut {
+        TxOut::new(50_000, Script::new(vec![0x76, 0xa9, 0x14]))
+    }
+
+    #[test]
+    fn script_decode_rejects_truncated_length_prefix() {
+        // CompactSize says 5 bytes follow, but only 2 are present.
+        let bytes = [0x05, 0xAA, 0xBB];
+        assert_eq!(
+            Script::from_bytes(&bytes),
+            Err(BitcoinError::InsufficientBytes)
+        );
+    }
+
+    #[test]
+    fn script_decode_rejects_oversized_length_prefix_without_allocating() {
+        // A CompactSize of u64::MAX must not be trusted as an allocation size.
+        let mut bytes = vec![0xFF];
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            Script::from_bytes(&bytes),
+            Err(BitcoinError::InsufficientBytes)
+        );
+    }
+
+    #[test]
+    fn witness_decode_rejects_oversized_item_length_without_allocating() {
+        // One witness item whose declared length is u64::MAX.
+        let mut bytes = vec![0x01, 0xFF];
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            Witness::from_bytes(&bytes),
+            Err(BitcoinError::InsufficientBytes)
+        );
+    }
+
+    #[test]
+    fn legacy_transaction_round_trips() {
+        let tx = BitcoinTransaction::new(1, vec![sample_input()], vec![sample_output()], 0);
+
+        let bytes = tx.to_bytes();
+        assert_eq!(bytes[4], 0x01, "legacy serialization carries no segwit marker");
+
+        let (decoded, consumed) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(decoded, tx);
+    }
+
+    #[test]
+    fn segwit_transaction_round_trips() {
+        let tx = BitcoinTransaction::new_with_witnesses(
+            1,
+            vec![sample_input()],
+            vec![sample_output()],
+            0,
+            vec![Witness::new(vec![vec![0xAA, 0xBB], vec![0xCC]])],
+        );
+
+        let bytes = tx.to_bytes();
+        assert_eq!(&bytes[4..6], &[SEGWIT_MARKER, SEGWIT_FLAG]);
+
+        let (decoded, consumed) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(decoded, tx);
+        assert_eq!(decoded.txid(), tx.txid());
+        assert_ne!(tx.txid(), tx.wtxid(), "segwit txid and wtxid must differ");
+    }
+
+    #[test]
+    fn block_header_round_trips() {
+        let header = BlockHeader::new(1, [1u8; 32], [2u8; 32], 3, 0x1d00ffff, 5);
+        let bytes = header.to_bytes();
+        assert_eq!(bytes.len(), 80);
+
+        let (decoded, consumed) = BlockHeader::from_bytes(&bytes).unwrap();
+        assert_eq!(consumed, 80);
+        assert_eq!(decoded, header);
+    }
+
+    #[test]
+    fn target_expands_known_compact_bits() {
+        // Genesis block difficulty: exponent 0x1d, mantissa 0x00ffff.
+        let header = BlockHeader::new(1, [0; 32], [0; 32], 0, 0x1d00ffff, 0);
+        let mut expected = [0u8; 32];
+        expected[4] = 0xff;
+        expected[5] = 0xff;
+        assert_eq!(header.target(), expected);
+    }
+
+    #[test]
+    fn target_is_zero_when_mantissa_sign_bit_set() {
+        // Mantissa 0x923456 has its top bit set (> 0x7FFFFF), so the target
+        // must clamp to zero regardless of the exponent.
+        let header = BlockHeader::new(1, [0; 32], [0; 32], 0, 0x04923456, 0);
+        assert_eq!(header.target(), [0u8; 32]);
+    }
+
+    #[test]
+    fn validate_pow_passes_against_a_maximally_easy_target() {
+        let header = BlockHeader::new(1, [9; 32], [9; 32], 12345, 0x207fffff, 0);
+        assert!(header.validate_pow());
+    }
+
+    #[test]
+    fn validate_pow_fails_against_an_impossible_target() {
+        // Mantissa sign bit set clamps the target to zero: no hash can satisfy it.
+        let header = BlockHeader::new(1, [9; 32], [9; 32], 12345, 0x04923456, 0);
+        assert!(!header.validate_pow());
+    }
 }
\ No newline at end of file